@@ -1,23 +1,66 @@
+// This module is the crate's HTTP library surface; main.rs's demo CLI only
+// exercises a slice of it, so dead_code would otherwise flag public API
+// meant for other callers.
+#![allow(dead_code)]
+
 use std::collections::HashMap;
 use std::io::prelude::*;
 use std::io::{BufReader, BufWriter};
 use thiserror::Error;
 
+// Variant names mirror the HTTP method tokens verbatim (RFC 9110), so the
+// all-caps spelling is intentional rather than an acronym.
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum HttpMethod {
     GET,
+    HEAD,
     POST,
+    PUT,
+    DELETE,
+    CONNECT,
+    OPTIONS,
+    TRACE,
+    PATCH,
 }
 
-impl HttpMethod {
-    pub fn to_string(&self) -> String {
-        match self {
-            HttpMethod::GET => "GET".to_string(),
-            HttpMethod::POST => "POST".to_string(),
+impl std::fmt::Display for HttpMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            HttpMethod::GET => "GET",
+            HttpMethod::HEAD => "HEAD",
+            HttpMethod::POST => "POST",
+            HttpMethod::PUT => "PUT",
+            HttpMethod::DELETE => "DELETE",
+            HttpMethod::CONNECT => "CONNECT",
+            HttpMethod::OPTIONS => "OPTIONS",
+            HttpMethod::TRACE => "TRACE",
+            HttpMethod::PATCH => "PATCH",
+        };
+        f.write_str(s)
+    }
+}
+
+impl TryFrom<&str> for HttpMethod {
+    type Error = HttpError;
+
+    fn try_from(v: &str) -> Result<Self, Self::Error> {
+        match v {
+            "GET" => Ok(Self::GET),
+            "HEAD" => Ok(Self::HEAD),
+            "POST" => Ok(Self::POST),
+            "PUT" => Ok(Self::PUT),
+            "DELETE" => Ok(Self::DELETE),
+            "CONNECT" => Ok(Self::CONNECT),
+            "OPTIONS" => Ok(Self::OPTIONS),
+            "TRACE" => Ok(Self::TRACE),
+            "PATCH" => Ok(Self::PATCH),
+            _ => Err(HttpError::UnknownMethod(v.to_string())),
         }
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum HttpVersion {
     HTTP1_0,
@@ -47,56 +90,284 @@ impl From<&str> for HttpVersion {
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum HttpStatus {
+    // 1xx
+    Continue,
+    SwitchingProtocols,
+    // 2xx
     Ok,
+    Created,
+    Accepted,
+    NoContent,
+    // 3xx
+    MovedPermanently,
+    Found,
+    NotModified,
+    // 4xx
+    BadRequest,
+    Unauthorized,
+    Forbidden,
     NotFound,
-    Invalid,
+    MethodNotAllowed,
+    RequestTimeout,
+    Conflict,
+    Gone,
+    LengthRequired,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    UnprocessableEntity,
+    TooManyRequests,
+    // 5xx
+    InternalServerError,
+    NotImplemented,
+    BadGateway,
+    ServiceUnavailable,
+    GatewayTimeout,
+    /// A well-formed but otherwise unrecognized status code (e.g. an
+    /// application-specific 499), carried through verbatim rather than
+    /// being destroyed.
+    Other(u32),
 }
 
 impl HttpStatus {
     pub fn code(&self) -> u32 {
         match self {
+            Self::Continue => 100,
+            Self::SwitchingProtocols => 101,
             Self::Ok => 200,
+            Self::Created => 201,
+            Self::Accepted => 202,
+            Self::NoContent => 204,
+            Self::MovedPermanently => 301,
+            Self::Found => 302,
+            Self::NotModified => 304,
+            Self::BadRequest => 400,
+            Self::Unauthorized => 401,
+            Self::Forbidden => 403,
             Self::NotFound => 404,
-            _ => 0,
+            Self::MethodNotAllowed => 405,
+            Self::RequestTimeout => 408,
+            Self::Conflict => 409,
+            Self::Gone => 410,
+            Self::LengthRequired => 411,
+            Self::PayloadTooLarge => 413,
+            Self::UnsupportedMediaType => 415,
+            Self::UnprocessableEntity => 422,
+            Self::TooManyRequests => 429,
+            Self::InternalServerError => 500,
+            Self::NotImplemented => 501,
+            Self::BadGateway => 502,
+            Self::ServiceUnavailable => 503,
+            Self::GatewayTimeout => 504,
+            Self::Other(code) => *code,
         }
     }
 
-    pub fn string(&self) -> &str {
+    /// The standard reason phrase for this status, or an empty string for
+    /// an `Other` code with none on record.
+    pub fn reason_phrase(&self) -> &str {
         match self {
+            Self::Continue => "Continue",
+            Self::SwitchingProtocols => "Switching Protocols",
             Self::Ok => "OK",
+            Self::Created => "Created",
+            Self::Accepted => "Accepted",
+            Self::NoContent => "No Content",
+            Self::MovedPermanently => "Moved Permanently",
+            Self::Found => "Found",
+            Self::NotModified => "Not Modified",
+            Self::BadRequest => "Bad Request",
+            Self::Unauthorized => "Unauthorized",
+            Self::Forbidden => "Forbidden",
             Self::NotFound => "Not Found",
-            _ => "",
+            Self::MethodNotAllowed => "Method Not Allowed",
+            Self::RequestTimeout => "Request Timeout",
+            Self::Conflict => "Conflict",
+            Self::Gone => "Gone",
+            Self::LengthRequired => "Length Required",
+            Self::PayloadTooLarge => "Payload Too Large",
+            Self::UnsupportedMediaType => "Unsupported Media Type",
+            Self::UnprocessableEntity => "Unprocessable Entity",
+            Self::TooManyRequests => "Too Many Requests",
+            Self::InternalServerError => "Internal Server Error",
+            Self::NotImplemented => "Not Implemented",
+            Self::BadGateway => "Bad Gateway",
+            Self::ServiceUnavailable => "Service Unavailable",
+            Self::GatewayTimeout => "Gateway Timeout",
+            Self::Other(_) => "",
         }
     }
 }
 
-impl From<u32> for HttpStatus {
-    fn from(v: u32) -> Self {
-        match v {
+impl TryFrom<u32> for HttpStatus {
+    type Error = HttpError;
+
+    /// Maps a numeric status code to its named variant. A code outside the
+    /// well-formed `100..=599` range is rejected; one inside that range but
+    /// not individually named here comes back as `Other` rather than an
+    /// error, preserving round-trip fidelity for codes this crate doesn't
+    /// recognize by name.
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        Ok(match v {
+            100 => Self::Continue,
+            101 => Self::SwitchingProtocols,
             200 => Self::Ok,
+            201 => Self::Created,
+            202 => Self::Accepted,
+            204 => Self::NoContent,
+            301 => Self::MovedPermanently,
+            302 => Self::Found,
+            304 => Self::NotModified,
+            400 => Self::BadRequest,
+            401 => Self::Unauthorized,
+            403 => Self::Forbidden,
             404 => Self::NotFound,
-            _ => Self::Invalid,
-        }
+            405 => Self::MethodNotAllowed,
+            408 => Self::RequestTimeout,
+            409 => Self::Conflict,
+            410 => Self::Gone,
+            411 => Self::LengthRequired,
+            413 => Self::PayloadTooLarge,
+            415 => Self::UnsupportedMediaType,
+            422 => Self::UnprocessableEntity,
+            429 => Self::TooManyRequests,
+            500 => Self::InternalServerError,
+            501 => Self::NotImplemented,
+            502 => Self::BadGateway,
+            503 => Self::ServiceUnavailable,
+            504 => Self::GatewayTimeout,
+            v if (100..=599).contains(&v) => Self::Other(v),
+            _ => return Err(HttpError::UnknownStatus(v)),
+        })
     }
 }
 
-impl From<&str> for HttpStatus {
-    fn from(v: &str) -> Self {
-        match v {
-            "200" => Self::Ok,
-            "404" => Self::NotFound,
-            _ => Self::Invalid,
-        }
+impl TryFrom<&str> for HttpStatus {
+    type Error = HttpError;
+
+    fn try_from(v: &str) -> Result<Self, Self::Error> {
+        let code: u32 = v.parse().map_err(|_| HttpError::HttpSyntaxError)?;
+        HttpStatus::try_from(code)
     }
 }
 
+/// A bidirectional transport a request/response can be exchanged over:
+/// a plaintext `TcpStream` or a TLS-wrapped one. `HttpClient`/`HttpServer`
+/// hold one of these as `Box<dyn Connection>` so the rest of their logic
+/// stays oblivious to which it is.
+pub trait Connection: Read + Write {}
+impl<T: Read + Write> Connection for T {}
+
+/// Wraps a TLS handshake/IO error as an `HttpError::IOError`, so callers
+/// don't need a dedicated TLS error variant to propagate one with `?`.
+pub fn tls_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> HttpError {
+    HttpError::from(std::io::Error::other(e))
+}
+
 #[derive(Debug)]
 pub struct HttpRequest {
     pub method: HttpMethod,
     pub path: String,
     pub version: HttpVersion,
     pub headers: HttpHeaders,
-    pub body: Vec<u8>,
+    pub body: Box<dyn MessageBody>,
+    pub params: HashMap<String, String>,
+    pub query: QueryString,
+}
+
+impl HttpRequest {
+    /// The first value of `key` in the request's query string, if any.
+    pub fn query(&self, key: &str) -> Option<&str> {
+        self.query.get(key)
+    }
+
+    /// All values of `key` in the request's query string, in the order
+    /// they appeared.
+    pub fn query_all(&self, key: &str) -> &[String] {
+        self.query.get_all(key)
+    }
+}
+
+/// A request's parsed, percent-decoded, multi-valued query parameters
+/// (the part of the request target after the first `?`).
+#[derive(Debug, Clone, Default)]
+pub struct QueryString {
+    params: HashMap<String, Vec<String>>,
+}
+
+impl QueryString {
+    pub fn empty() -> QueryString {
+        QueryString {
+            params: HashMap::new(),
+        }
+    }
+
+    /// Parses `&`-separated `key=value` pairs (bare keys map to an empty
+    /// value), percent-decoding each with `+` treated as a space. Returns
+    /// `HttpSyntaxError` on a malformed `%XX` escape rather than panicking.
+    pub fn parse(raw: &str) -> Result<QueryString, HttpError> {
+        let mut params: HashMap<String, Vec<String>> = HashMap::new();
+        for pair in raw.split('&') {
+            if pair.is_empty() {
+                continue;
+            }
+            let mut iter = pair.splitn(2, '=');
+            let key = percent_decode(iter.next().unwrap_or(""))?;
+            let value = match iter.next() {
+                Some(v) => percent_decode(v)?,
+                None => String::new(),
+            };
+            params.entry(key).or_default().push(value);
+        }
+        Ok(QueryString { params })
+    }
+
+    /// The first value stored for `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.params
+            .get(key)
+            .and_then(|values| values.first())
+            .map(|v| v.as_str())
+    }
+
+    /// All values stored for `key`, in the order they were parsed.
+    pub fn get_all(&self, key: &str) -> &[String] {
+        self.params.get(key).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+}
+
+/// Percent-decodes a `x-www-form-urlencoded` component: `+` becomes a
+/// space and `%XX` escapes are decoded byte-for-byte before the result is
+/// validated as UTF-8.
+fn percent_decode(s: &str) -> Result<String, HttpError> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b'%' => {
+                let hex = bytes
+                    .get(i + 1..i + 3)
+                    .ok_or(HttpError::HttpSyntaxError)?;
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return Err(HttpError::HttpSyntaxError);
+                }
+                let hex_str = std::str::from_utf8(hex).map_err(|_| HttpError::HttpSyntaxError)?;
+                let byte =
+                    u8::from_str_radix(hex_str, 16).map_err(|_| HttpError::HttpSyntaxError)?;
+                out.push(byte);
+                i += 3;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).map_err(|_| HttpError::HttpSyntaxError)
 }
 
 #[derive(Debug)]
@@ -104,7 +375,73 @@ pub struct HttpResponse {
     pub version: HttpVersion,
     pub status: HttpStatus,
     pub headers: HttpHeaders,
-    pub body: Vec<u8>,
+    pub body: Box<dyn MessageBody>,
+}
+
+/// How large a `MessageBody` is, for framing purposes: `Empty` sends no
+/// body at all, `Sized` emits `content-length`, `Stream` emits
+/// `transfer-encoding: chunked` since the total size isn't known up front.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BodySize {
+    Empty,
+    Sized(usize),
+    Stream,
+}
+
+/// A message body a caller can pull in chunks instead of buffering it all
+/// in memory up front, so a handler can stream a file or a generated
+/// response without knowing its length ahead of time.
+pub trait MessageBody: std::fmt::Debug {
+    fn size(&self) -> BodySize;
+    fn next_chunk(&mut self) -> Option<std::io::Result<Vec<u8>>>;
+}
+
+impl MessageBody for Vec<u8> {
+    fn size(&self) -> BodySize {
+        if self.is_empty() {
+            BodySize::Empty
+        } else {
+            BodySize::Sized(self.len())
+        }
+    }
+
+    fn next_chunk(&mut self) -> Option<std::io::Result<Vec<u8>>> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(Ok(std::mem::take(self)))
+        }
+    }
+}
+
+impl MessageBody for &'static str {
+    fn size(&self) -> BodySize {
+        if self.is_empty() {
+            BodySize::Empty
+        } else {
+            BodySize::Sized(self.len())
+        }
+    }
+
+    fn next_chunk(&mut self) -> Option<std::io::Result<Vec<u8>>> {
+        if self.is_empty() {
+            None
+        } else {
+            let chunk = self.as_bytes().to_vec();
+            *self = "";
+            Some(Ok(chunk))
+        }
+    }
+}
+
+/// Drains a `MessageBody` into a single buffer. Used where the whole body
+/// has to be held in memory regardless (e.g. to compress it).
+pub fn read_all_chunks(body: &mut dyn MessageBody) -> std::io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    while let Some(chunk) = body.next_chunk() {
+        buf.extend_from_slice(&chunk?);
+    }
+    Ok(buf)
 }
 
 #[derive(Error, Debug)]
@@ -116,11 +453,150 @@ pub enum HttpError {
     },
     #[error("syntax error")]
     HttpSyntaxError,
+    #[error("unknown method: {0}")]
+    UnknownMethod(String),
+    #[error("unknown status: {0}")]
+    UnknownStatus(u32),
+    #[error("header line too long")]
+    LineTooLong,
+    #[error("too many headers")]
+    TooManyHeaders,
+}
+
+/// Maximum number of lines `Parser` accumulates before the blank line
+/// terminating the head, guarding against a peer that never sends one.
+const MAX_HEADER_COUNT: usize = 100;
+
+/// Maximum length of a single line (including its terminating `\r\n` or
+/// `\n`) `Parser` accepts, guarding against an unbounded line.
+const MAX_LINE_LENGTH: usize = 8192;
+
+/// Outcome of feeding bytes into a `Parser`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseStatus {
+    /// The blank line terminating the head was found. The `usize` is how
+    /// many bytes of the fed buffer belong to the head, so a caller reading
+    /// from a shared stream knows where the body (if any) starts.
+    Complete(usize),
+    /// The fed buffer ended mid-line or mid-head; call `parse` again with
+    /// more bytes once they're available.
+    Partial,
+}
+
+/// A push-style, incremental parser for an HTTP message head (a request or
+/// status line followed by header lines, terminated by a blank line) that
+/// tolerates the underlying bytes arriving in arbitrary fragments instead
+/// of assuming a full line is always available in one read. `parse` can be
+/// called repeatedly as bytes trickle in; `read_head` is a thin blocking
+/// wrapper over it for one-shot callers.
+#[derive(Debug, Default)]
+pub struct Parser {
+    partial_line: Vec<u8>,
+    lines: Vec<String>,
+    done: bool,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser::default()
+    }
+
+    /// Feeds another chunk of bytes, returning `Complete(consumed)` once
+    /// the blank line terminating the head has been seen, or `Partial` if
+    /// `buf` ran out first. Once `Complete`, further calls are no-ops.
+    pub fn parse(&mut self, buf: &[u8]) -> Result<ParseStatus, HttpError> {
+        if self.done {
+            return Ok(ParseStatus::Complete(0));
+        }
+
+        let mut consumed = 0;
+        while let Some(offset) = buf[consumed..].iter().position(|&b| b == b'\n') {
+            let line_end = consumed + offset + 1;
+            if self.partial_line.len() + (line_end - consumed) > MAX_LINE_LENGTH {
+                return Err(HttpError::LineTooLong);
+            }
+            self.partial_line.extend_from_slice(&buf[consumed..line_end]);
+            consumed = line_end;
+
+            let line = String::from_utf8(std::mem::take(&mut self.partial_line))
+                .map_err(|_| HttpError::HttpSyntaxError)?;
+            let line = line.trim_end_matches("\r\n").trim_end_matches('\n').to_string();
+
+            if line.is_empty() {
+                self.done = true;
+                return Ok(ParseStatus::Complete(consumed));
+            }
+
+            if self.lines.len() >= MAX_HEADER_COUNT {
+                return Err(HttpError::TooManyHeaders);
+            }
+            self.lines.push(line);
+        }
+
+        if self.partial_line.len() + (buf.len() - consumed) > MAX_LINE_LENGTH {
+            return Err(HttpError::LineTooLong);
+        }
+        self.partial_line.extend_from_slice(&buf[consumed..]);
+        Ok(ParseStatus::Partial)
+    }
+
+    /// The lines accumulated so far, in order, once `parse` has returned
+    /// `Complete` (for a request/status line + headers, the first line is
+    /// the request/status line and the rest are header lines).
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+}
+
+/// Outcome of `read_head`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Head {
+    /// The connection closed before any bytes of a new message arrived —
+    /// a graceful close between keep-alive requests, as opposed to a
+    /// connection that closes partway through one.
+    Closed,
+    /// `lines[0]` is the request/status line; the rest are header lines.
+    Lines(Vec<String>),
 }
 
+/// Blocking wrapper over `Parser`: reads from `reader` in whatever chunks
+/// happen to be available, feeding each into the parser until it reports
+/// the blank line terminating the head. Unlike reading the request/status
+/// line with a plain `read_line`, this keeps it subject to the same
+/// `MAX_LINE_LENGTH`/`MAX_HEADER_COUNT` guards as the header block.
+pub fn read_head<T: Read>(reader: &mut BufReader<T>) -> Result<Head, HttpError> {
+    let mut parser = Parser::new();
+    let mut started = false;
+    loop {
+        let buf = reader.fill_buf().map_err(HttpError::from)?;
+        if buf.is_empty() {
+            return if started {
+                Err(HttpError::HttpSyntaxError)
+            } else {
+                Ok(Head::Closed)
+            };
+        }
+        started = true;
+
+        match parser.parse(buf)? {
+            ParseStatus::Complete(consumed) => {
+                reader.consume(consumed);
+                break;
+            }
+            ParseStatus::Partial => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    }
+    Ok(Head::Lines(parser.lines().to_vec()))
+}
+
+/// Headers are a multimap: names like `Set-Cookie` legitimately repeat, and
+/// clobbering earlier values on a duplicate would silently drop them.
 #[derive(Debug)]
 pub struct HttpHeaders {
-    headers: HashMap<String, String>,
+    headers: HashMap<String, Vec<String>>,
 }
 
 impl HttpHeaders {
@@ -130,52 +606,530 @@ impl HttpHeaders {
         }
     }
 
+    /// Writes one line per stored value. Values of one name come out in the
+    /// order they were appended; the order across different names is not
+    /// defined.
     pub fn write_to<T: Write>(&self, writer: &mut BufWriter<T>) -> std::io::Result<()> {
-        for (key, value) in &self.headers {
-            write!(writer, "{}: {}\r\n", key, value)?;
+        for (key, values) in &self.headers {
+            for value in values {
+                write!(writer, "{}: {}\r\n", key, value)?;
+            }
         }
         Ok(())
     }
 
-    pub fn read_from<T: Read>(&mut self, reader: &mut BufReader<T>) -> Result<(), HttpError> {
-        loop {
-            let mut line = String::new();
-            let size = reader.read_line(&mut line).map_err(HttpError::from)?;
-            if size == 0 {
-                return Err(HttpError::HttpSyntaxError);
-            }
-
-            let line_str = line.trim_end_matches("\r\n");
-            if line_str == "" {
-                break;
-            }
-            let mut iter = line_str.splitn(2, ":");
+    /// Populates headers from already-split header lines, as produced by
+    /// `read_head`.
+    pub fn parse_lines(&mut self, lines: &[String]) -> Result<(), HttpError> {
+        for line in lines {
+            let mut iter = line.splitn(2, ":");
             let key = iter
                 .next()
-                .ok_or_else(|| HttpError::HttpSyntaxError)?
+                .ok_or(HttpError::HttpSyntaxError)?
                 .to_ascii_lowercase();
             let value = iter
                 .next()
-                .ok_or_else(|| HttpError::HttpSyntaxError)?
+                .ok_or(HttpError::HttpSyntaxError)?
                 .trim_start()
                 .to_string();
 
-            self.headers.insert(key, value);
+            self.append(key, value);
         }
         Ok(())
     }
 
     pub fn content_length(&self) -> Option<usize> {
-        match self.headers.get(&"content-length".to_string()) {
-            Some(v) => match v.parse::<usize>() {
-                Ok(s) => Some(s),
-                Err(_) => None,
-            },
+        match self.get_first(&"content-length".to_string()) {
+            Some(v) => v.parse::<usize>().ok(),
             None => None,
         }
     }
 
+    pub fn is_chunked(&self) -> bool {
+        match self.get_first(&"transfer-encoding".to_string()) {
+            Some(v) => match v.split(',').next_back() {
+                Some(last) => last.trim().eq_ignore_ascii_case("chunked"),
+                None => false,
+            },
+            None => false,
+        }
+    }
+
     pub fn contains_key(&self, key: &String) -> bool {
         self.headers.contains_key(key)
     }
+
+    /// The first value stored for `key`, if any.
+    pub fn get_first(&self, key: &String) -> Option<&str> {
+        self.headers
+            .get(key)
+            .and_then(|values| values.first())
+            .map(|v| v.as_str())
+    }
+
+    /// All values stored for `key`, in the order they were appended.
+    pub fn get_all(&self, key: &str) -> &[String] {
+        self.headers.get(key).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /// Replaces any existing values for `key` with a single `value`.
+    pub fn insert(&mut self, key: String, value: String) {
+        self.headers.insert(key.to_ascii_lowercase(), vec![value]);
+    }
+
+    /// Adds `value` for `key` without removing any existing values for it.
+    pub fn append(&mut self, key: String, value: String) {
+        self.headers
+            .entry(key.to_ascii_lowercase())
+            .or_default()
+            .push(value);
+    }
+}
+
+/// How a message body's end is determined, picked from its headers before
+/// any bytes of the body are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyReader {
+    /// No body at all: `content-length: 0`, or neither header present on
+    /// a request.
+    Empty,
+    /// Exactly `usize` bytes follow, per `content-length`.
+    Sized(usize),
+    /// `transfer-encoding: chunked` framing.
+    Chunked,
+    /// No framing header at all; read until the connection closes. Only
+    /// meaningful for responses, where it's the HTTP/1.0 default.
+    Eof,
+}
+
+impl BodyReader {
+    /// Picks the framing for a request body. Requests never fall back to
+    /// `Eof`: with no `content-length` and no chunked encoding there is no
+    /// body to read.
+    pub fn for_request(headers: &HttpHeaders) -> BodyReader {
+        Self::from_headers(headers, BodyReader::Empty)
+    }
+
+    /// Picks the framing for a response body, falling back to `Eof` when
+    /// neither `content-length` nor `transfer-encoding: chunked` is present.
+    pub fn for_response(headers: &HttpHeaders) -> BodyReader {
+        Self::from_headers(headers, BodyReader::Eof)
+    }
+
+    fn from_headers(headers: &HttpHeaders, no_length_fallback: BodyReader) -> BodyReader {
+        if headers.is_chunked() {
+            BodyReader::Chunked
+        } else {
+            match headers.content_length() {
+                Some(0) => BodyReader::Empty,
+                Some(n) => BodyReader::Sized(n),
+                None => no_length_fallback,
+            }
+        }
+    }
+}
+
+/// Reads a message body according to the framing `kind` already decided by
+/// `BodyReader::for_request`/`for_response`.
+pub fn read_body<T: Read>(
+    reader: &mut BufReader<T>,
+    kind: BodyReader,
+) -> Result<Vec<u8>, HttpError> {
+    match kind {
+        BodyReader::Empty => Ok(Vec::new()),
+        BodyReader::Sized(n) => {
+            let mut body = vec![0u8; n];
+            reader.read_exact(&mut body).map_err(HttpError::from)?;
+            Ok(body)
+        }
+        BodyReader::Chunked => read_chunked_body(reader),
+        BodyReader::Eof => {
+            let mut body = Vec::new();
+            reader.read_to_end(&mut body).map_err(HttpError::from)?;
+            Ok(body)
+        }
+    }
+}
+
+/// Reads a `transfer-encoding: chunked` body: a sequence of
+/// `{hex size}[;ext]\r\n{data}\r\n` chunks terminated by a zero-size chunk,
+/// followed by optional trailer headers up to the blank line.
+pub fn read_chunked_body<T: Read>(reader: &mut BufReader<T>) -> Result<Vec<u8>, HttpError> {
+    let mut body = Vec::new();
+    loop {
+        let mut size_line = String::new();
+        reader
+            .read_line(&mut size_line)
+            .map_err(HttpError::from)?;
+        let size_str = size_line.trim_end_matches("\r\n").trim_end_matches('\n');
+        let size_str = match size_str.split(';').next() {
+            Some(s) => s,
+            None => size_str,
+        };
+        let size = usize::from_str_radix(size_str.trim(), 16)
+            .map_err(|_| HttpError::HttpSyntaxError)?;
+
+        if size == 0 {
+            // consume optional trailer headers up to the blank line
+            loop {
+                let mut trailer_line = String::new();
+                reader
+                    .read_line(&mut trailer_line)
+                    .map_err(HttpError::from)?;
+                if trailer_line
+                    .trim_end_matches("\r\n")
+                    .trim_end_matches('\n')
+                    .is_empty()
+                {
+                    break;
+                }
+            }
+            break;
+        }
+
+        let mut chunk = vec![0u8; size];
+        reader.read_exact(&mut chunk).map_err(HttpError::from)?;
+        body.extend_from_slice(&chunk);
+
+        let mut crlf = [0u8; 2];
+        reader.read_exact(&mut crlf).map_err(HttpError::from)?;
+    }
+    Ok(body)
+}
+
+/// Writes a single `transfer-encoding: chunked` frame: `{hex len}\r\n{bytes}\r\n`.
+/// An empty chunk is a no-op; use `finish_chunked` to terminate the stream.
+pub fn write_chunk<T: Write>(writer: &mut BufWriter<T>, chunk: &[u8]) -> std::io::Result<()> {
+    if chunk.is_empty() {
+        return Ok(());
+    }
+    write!(writer, "{:x}\r\n", chunk.len())?;
+    writer.write_all(chunk)?;
+    write!(writer, "\r\n")?;
+    Ok(())
+}
+
+/// Writes the terminating zero-size chunk that ends a chunked body.
+pub fn finish_chunked<T: Write>(writer: &mut BufWriter<T>) -> std::io::Result<()> {
+    write!(writer, "0\r\n\r\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers_append_keeps_repeated_values() {
+        let mut headers = HttpHeaders::new();
+        headers.append("Set-Cookie".to_string(), "a=1".to_string());
+        headers.append("Set-Cookie".to_string(), "b=2".to_string());
+
+        assert_eq!(
+            &["a=1".to_string(), "b=2".to_string()],
+            headers.get_all("set-cookie")
+        );
+        assert_eq!(Some("a=1"), headers.get_first(&"set-cookie".to_string()));
+    }
+
+    #[test]
+    fn test_headers_insert_replaces_existing_values() {
+        let mut headers = HttpHeaders::new();
+        headers.append("X-Thing".to_string(), "one".to_string());
+        headers.insert("X-Thing".to_string(), "two".to_string());
+
+        assert_eq!(&["two".to_string()], headers.get_all("x-thing"));
+    }
+
+    #[test]
+    fn test_headers_keys_are_case_insensitive() {
+        let mut headers = HttpHeaders::new();
+        headers.insert("Content-Length".to_string(), "5".to_string());
+
+        assert!(headers.contains_key(&"content-length".to_string()));
+        assert_eq!(Some(5), headers.content_length());
+    }
+
+    #[test]
+    fn test_headers_is_chunked_reads_last_transfer_coding() {
+        let mut headers = HttpHeaders::new();
+        headers.insert("transfer-encoding".to_string(), "gzip, chunked".to_string());
+        assert!(headers.is_chunked());
+
+        headers.insert("transfer-encoding".to_string(), "gzip".to_string());
+        assert!(!headers.is_chunked());
+    }
+
+    #[test]
+    fn test_query_string_parse_decodes_plus_and_percent_escapes() {
+        let query = QueryString::parse("name=John+Doe&city=S%C3%A3o+Paulo").unwrap();
+
+        assert_eq!(Some("John Doe"), query.get("name"));
+        assert_eq!(Some("São Paulo"), query.get("city"));
+    }
+
+    #[test]
+    fn test_query_string_parse_collects_repeated_keys() {
+        let query = QueryString::parse("tag=a&tag=b&tag=c").unwrap();
+
+        assert_eq!(
+            &["a".to_string(), "b".to_string(), "c".to_string()],
+            query.get_all("tag")
+        );
+    }
+
+    #[test]
+    fn test_query_string_parse_treats_bare_key_as_empty_value() {
+        let query = QueryString::parse("flag").unwrap();
+        assert_eq!(Some(""), query.get("flag"));
+    }
+
+    #[test]
+    fn test_query_string_parse_rejects_malformed_percent_escape() {
+        assert!(matches!(
+            QueryString::parse("name=%zz"),
+            Err(HttpError::HttpSyntaxError)
+        ));
+        assert!(matches!(
+            QueryString::parse("name=%2"),
+            Err(HttpError::HttpSyntaxError)
+        ));
+    }
+
+    #[test]
+    fn test_query_string_empty_has_no_values() {
+        let query = QueryString::empty();
+        assert_eq!(None, query.get("anything"));
+        assert!(query.get_all("anything").is_empty());
+    }
+
+    #[test]
+    fn test_http_method_try_from_str_round_trips_every_variant() {
+        for (text, method) in [
+            ("GET", HttpMethod::GET),
+            ("HEAD", HttpMethod::HEAD),
+            ("POST", HttpMethod::POST),
+            ("PUT", HttpMethod::PUT),
+            ("DELETE", HttpMethod::DELETE),
+            ("CONNECT", HttpMethod::CONNECT),
+            ("OPTIONS", HttpMethod::OPTIONS),
+            ("TRACE", HttpMethod::TRACE),
+            ("PATCH", HttpMethod::PATCH),
+        ] {
+            assert_eq!(method, HttpMethod::try_from(text).unwrap());
+            assert_eq!(text, method.to_string());
+        }
+    }
+
+    #[test]
+    fn test_http_method_try_from_str_rejects_unknown_method() {
+        assert!(matches!(
+            HttpMethod::try_from("FETCH"),
+            Err(HttpError::UnknownMethod(m)) if m == "FETCH"
+        ));
+    }
+
+    #[test]
+    fn test_http_status_try_from_u32_maps_named_codes() {
+        assert_eq!(HttpStatus::Ok, HttpStatus::try_from(200u32).unwrap());
+        assert_eq!(200, HttpStatus::Ok.code());
+        assert_eq!("OK", HttpStatus::Ok.reason_phrase());
+    }
+
+    #[test]
+    fn test_http_status_try_from_u32_falls_back_to_other_in_range() {
+        let status = HttpStatus::try_from(499u32).unwrap();
+        assert_eq!(HttpStatus::Other(499), status);
+        assert_eq!(499, status.code());
+        assert_eq!("", status.reason_phrase());
+    }
+
+    #[test]
+    fn test_http_status_try_from_u32_rejects_out_of_range_codes() {
+        assert!(matches!(
+            HttpStatus::try_from(99u32),
+            Err(HttpError::UnknownStatus(99))
+        ));
+        assert!(matches!(
+            HttpStatus::try_from(600u32),
+            Err(HttpError::UnknownStatus(600))
+        ));
+    }
+
+    #[test]
+    fn test_http_status_try_from_str_parses_the_numeric_code() {
+        assert_eq!(HttpStatus::NotFound, HttpStatus::try_from("404").unwrap());
+        assert!(matches!(
+            HttpStatus::try_from("nope"),
+            Err(HttpError::HttpSyntaxError)
+        ));
+    }
+
+    #[test]
+    fn test_parser_completes_in_a_single_call() {
+        let mut parser = Parser::new();
+        let status = parser
+            .parse(b"GET / HTTP/1.1\r\nhost: example.com\r\n\r\n")
+            .unwrap();
+
+        assert_eq!(ParseStatus::Complete(37), status);
+        assert_eq!(
+            &["GET / HTTP/1.1".to_string(), "host: example.com".to_string()],
+            parser.lines()
+        );
+    }
+
+    #[test]
+    fn test_parser_tolerates_the_head_arriving_byte_by_byte() {
+        let head = b"GET / HTTP/1.1\r\nhost: example.com\r\n\r\n";
+        let mut parser = Parser::new();
+        let mut status = ParseStatus::Partial;
+        for &byte in head {
+            status = parser.parse(&[byte]).unwrap();
+        }
+
+        assert_eq!(ParseStatus::Complete(1), status);
+        assert_eq!(
+            &["GET / HTTP/1.1".to_string(), "host: example.com".to_string()],
+            parser.lines()
+        );
+    }
+
+    #[test]
+    fn test_parser_reports_partial_before_blank_line_seen() {
+        let mut parser = Parser::new();
+        let status = parser.parse(b"GET / HTTP/1.1\r\n").unwrap();
+        assert_eq!(ParseStatus::Partial, status);
+        assert_eq!(&["GET / HTTP/1.1".to_string()], parser.lines());
+    }
+
+    #[test]
+    fn test_parser_is_a_no_op_once_complete() {
+        let mut parser = Parser::new();
+        parser.parse(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(ParseStatus::Complete(0), parser.parse(b"more data\r\n").unwrap());
+    }
+
+    #[test]
+    fn test_parser_rejects_a_line_longer_than_the_limit() {
+        let mut parser = Parser::new();
+        let long_line = vec![b'a'; MAX_LINE_LENGTH + 1];
+        assert!(matches!(
+            parser.parse(&long_line),
+            Err(HttpError::LineTooLong)
+        ));
+    }
+
+    #[test]
+    fn test_parser_rejects_too_many_header_lines() {
+        let mut parser = Parser::new();
+        let mut head = "GET / HTTP/1.1\r\n".to_string();
+        for i in 0..=MAX_HEADER_COUNT {
+            head.push_str(&format!("x-{}: {}\r\n", i, i));
+        }
+        assert!(matches!(
+            parser.parse(head.as_bytes()),
+            Err(HttpError::TooManyHeaders)
+        ));
+    }
+
+    #[test]
+    fn test_body_reader_for_request_defaults_to_empty() {
+        let headers = HttpHeaders::new();
+        assert_eq!(BodyReader::Empty, BodyReader::for_request(&headers));
+    }
+
+    #[test]
+    fn test_body_reader_for_response_defaults_to_eof() {
+        let headers = HttpHeaders::new();
+        assert_eq!(BodyReader::Eof, BodyReader::for_response(&headers));
+    }
+
+    #[test]
+    fn test_body_reader_prefers_chunked_over_content_length() {
+        let mut headers = HttpHeaders::new();
+        headers.insert("content-length".to_string(), "5".to_string());
+        headers.insert("transfer-encoding".to_string(), "chunked".to_string());
+
+        assert_eq!(BodyReader::Chunked, BodyReader::for_request(&headers));
+    }
+
+    #[test]
+    fn test_body_reader_picks_sized_from_content_length() {
+        let mut headers = HttpHeaders::new();
+        headers.insert("content-length".to_string(), "5".to_string());
+        assert_eq!(BodyReader::Sized(5), BodyReader::for_request(&headers));
+    }
+
+    #[test]
+    fn test_read_body_reads_exactly_content_length_bytes() {
+        let mut reader = BufReader::new("hello-extra".as_bytes());
+        let body = read_body(&mut reader, BodyReader::Sized(5)).unwrap();
+        assert_eq!(b"hello".to_vec(), body);
+    }
+
+    #[test]
+    fn test_read_body_eof_reads_until_the_connection_closes() {
+        let mut reader = BufReader::new("the rest of the stream".as_bytes());
+        let body = read_body(&mut reader, BodyReader::Eof).unwrap();
+        assert_eq!(b"the rest of the stream".to_vec(), body);
+    }
+
+    #[test]
+    fn test_read_chunked_body_reassembles_chunks_and_stops_at_terminator() {
+        let raw = b"4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n";
+        let mut reader = BufReader::new(&raw[..]);
+        let body = read_body(&mut reader, BodyReader::Chunked).unwrap();
+        assert_eq!(b"Wikipedia".to_vec(), body);
+    }
+
+    #[test]
+    fn test_read_chunked_body_skips_trailer_headers() {
+        let raw = b"3\r\nabc\r\n0\r\nx-trailer: value\r\n\r\n";
+        let mut reader = BufReader::new(&raw[..]);
+        let body = read_body(&mut reader, BodyReader::Chunked).unwrap();
+        assert_eq!(b"abc".to_vec(), body);
+    }
+
+    #[test]
+    fn test_write_chunk_and_read_chunked_body_round_trip() {
+        let mut writer = BufWriter::new(Vec::new());
+        write_chunk(&mut writer, b"hello").unwrap();
+        finish_chunked(&mut writer).unwrap();
+        let raw = writer.into_inner().unwrap();
+
+        let mut reader = BufReader::new(&raw[..]);
+        let body = read_body(&mut reader, BodyReader::Chunked).unwrap();
+        assert_eq!(b"hello".to_vec(), body);
+    }
+
+    #[test]
+    fn test_read_head_splits_the_first_line_from_the_headers() {
+        let raw = b"GET / HTTP/1.1\r\nhost: example.com\r\n\r\n";
+        let mut reader = BufReader::new(&raw[..]);
+
+        match read_head(&mut reader).unwrap() {
+            Head::Lines(lines) => {
+                assert_eq!(
+                    vec!["GET / HTTP/1.1".to_string(), "host: example.com".to_string()],
+                    lines
+                );
+            }
+            Head::Closed => panic!("expected Head::Lines"),
+        }
+    }
+
+    #[test]
+    fn test_read_head_reports_closed_on_an_empty_connection() {
+        let raw: &[u8] = b"";
+        let mut reader = BufReader::new(raw);
+        assert_eq!(Head::Closed, read_head(&mut reader).unwrap());
+    }
+
+    #[test]
+    fn test_read_head_rejects_a_request_line_with_no_terminator_over_the_limit() {
+        let long_line = vec![b'a'; MAX_LINE_LENGTH + 1];
+        let mut reader = BufReader::new(&long_line[..]);
+        assert!(matches!(read_head(&mut reader), Err(HttpError::LineTooLong)));
+    }
 }
@@ -1,12 +1,41 @@
+// This module is the crate's HTTP library surface; main.rs's demo CLI only
+// exercises a slice of it, so dead_code would otherwise flag public API
+// meant for other callers.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
 use std::io::prelude::*;
 use std::io::{BufReader, BufWriter};
 use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression;
+use native_tls::{Identity, TlsAcceptor};
 
 use crate::common::*;
 
+/// Outcome of reading one request off the wire: a fully parsed request, a
+/// handler-issued early rejection of an `Expect: 100-continue` upload (the
+/// body was never read), or a graceful close (client sent no more data).
+enum RecvOutcome {
+    Request(HttpRequest),
+    Rejected(HttpResponse),
+    Closed,
+}
+
+/// A hook consulted when a client sends `Expect: 100-continue`, letting a
+/// handler reject the upload early instead of sending the interim response.
+type ExpectContinueHook = Box<dyn Fn(&HttpRequest) -> Option<HttpResponse> + Send + Sync>;
+
 pub struct HttpServer {
     listener: TcpListener,
     handler: Box<dyn Handler>,
+    read_timeout: Option<Duration>,
+    compression_enabled: bool,
+    expect_continue_hook: Option<ExpectContinueHook>,
+    tls_acceptor: Option<Arc<TlsAcceptor>>,
 }
 
 impl HttpServer {
@@ -16,86 +45,361 @@ impl HttpServer {
     ) -> Result<HttpServer, HttpError> {
         let listener = TcpListener::bind(addr).map_err(HttpError::from)?;
         Ok(HttpServer {
-            listener: listener,
-            handler: handler,
+            listener,
+            handler,
+            read_timeout: None,
+            compression_enabled: false,
+            expect_continue_hook: None,
+            tls_acceptor: None,
         })
     }
 
+    /// Binds like `new`, but every accepted connection is wrapped in a
+    /// server-side TLS session (handshaking with `identity`) before any
+    /// request is read off it.
+    pub fn new_tls<T: ToSocketAddrs>(
+        addr: T,
+        handler: Box<dyn Handler>,
+        identity: Identity,
+    ) -> Result<HttpServer, HttpError> {
+        let listener = TcpListener::bind(addr).map_err(HttpError::from)?;
+        let acceptor = TlsAcceptor::new(identity).map_err(tls_error)?;
+        Ok(HttpServer {
+            listener,
+            handler,
+            read_timeout: None,
+            compression_enabled: false,
+            expect_continue_hook: None,
+            tls_acceptor: Some(Arc::new(acceptor)),
+        })
+    }
+
+    /// Sets the socket read timeout of every accepted connection: the
+    /// longest any single read of a request (headers and, if present, the
+    /// body) may wait for data. A read that times out is answered with
+    /// `408 Request Timeout` and the connection is closed.
+    pub fn with_read_timeout(mut self, timeout: Duration) -> HttpServer {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Enables transparent gzip/deflate compression of response bodies,
+    /// negotiated against the request's `Accept-Encoding` header.
+    pub fn with_compression(mut self) -> HttpServer {
+        self.compression_enabled = true;
+        self
+    }
+
+    /// Installs a hook consulted whenever a client sends `Expect:
+    /// 100-continue` before uploading a body. Returning `Some(response)`
+    /// rejects the upload (e.g. body too large) and suppresses the interim
+    /// `100 Continue`; returning `None` lets the upload proceed normally.
+    pub fn with_expect_continue_hook(
+        mut self,
+        hook: impl Fn(&HttpRequest) -> Option<HttpResponse> + Send + Sync + 'static,
+    ) -> HttpServer {
+        self.expect_continue_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Accepts connections forever. A single connection's error (a TLS
+    /// handshake failure, a malformed request, a client that disconnects
+    /// mid-request) is logged and the
+    /// loop moves on to the next connection rather than tearing down the
+    /// whole server; only a failure of the listener itself is propagated.
     pub fn listen(&self) -> Result<(), HttpError> {
         for stream in self.listener.incoming() {
-            self.handle(stream.map_err(HttpError::from)?)?;
+            let stream = stream.map_err(HttpError::from)?;
+            if let Err(e) = self.handle(stream) {
+                eprintln!("connection error: {}", e);
+            }
         }
         Ok(())
     }
 
     fn handle(&self, stream: TcpStream) -> Result<(), HttpError> {
-        let mut req = Self::recv(BufReader::new(&stream))?;
-        let resp = self.handler.handle(&mut req)?;
-        Self::send(&resp, BufWriter::new(&stream)).map_err(HttpError::from)?;
-        Ok(())
+        stream
+            .set_read_timeout(self.read_timeout)
+            .map_err(HttpError::from)?;
+
+        let mut conn: Box<dyn Connection> = match &self.tls_acceptor {
+            Some(acceptor) => Box::new(acceptor.accept(stream).map_err(tls_error)?),
+            None => Box::new(stream),
+        };
+        let mut reader = BufReader::new(&mut *conn);
+
+        loop {
+            let req = match self.recv(&mut reader) {
+                Ok(RecvOutcome::Request(req)) => req,
+                Ok(RecvOutcome::Rejected(mut resp)) => {
+                    let placeholder_req = HttpRequest {
+                        method: HttpMethod::GET,
+                        path: String::new(),
+                        version: HttpVersion::HTTP1_1,
+                        headers: HttpHeaders::new(),
+                        body: Box::new(Vec::new()),
+                        params: HashMap::new(),
+                        query: QueryString::empty(),
+                    };
+                    self.send(&placeholder_req, &mut resp, BufWriter::new(reader.get_mut()))
+                        .map_err(HttpError::from)?;
+                    return Ok(());
+                }
+                Ok(RecvOutcome::Closed) => return Ok(()),
+                Err(HttpError::IOError { source }) if is_timeout(&source) => {
+                    let timeout_req = HttpRequest {
+                        method: HttpMethod::GET,
+                        path: String::new(),
+                        version: HttpVersion::HTTP1_1,
+                        headers: HttpHeaders::new(),
+                        body: Box::new(Vec::new()),
+                        params: HashMap::new(),
+                        query: QueryString::empty(),
+                    };
+                    let mut resp = HttpResponse {
+                        version: HttpVersion::HTTP1_1,
+                        status: HttpStatus::RequestTimeout,
+                        headers: HttpHeaders::new(),
+                        body: Box::new(Vec::new()),
+                    };
+                    return self
+                        .send(&timeout_req, &mut resp, BufWriter::new(reader.get_mut()))
+                        .map_err(HttpError::from);
+                }
+                Err(e) => return Err(e),
+            };
+
+            let keep_alive = wants_keep_alive(&req);
+
+            let mut req = req;
+            let mut resp = self.handler.handle(&mut req)?;
+            if !resp.headers.contains_key(&"connection".to_string()) {
+                resp.headers.insert(
+                    "connection".to_string(),
+                    (if keep_alive { "keep-alive" } else { "close" }).to_string(),
+                );
+            }
+
+            self.send(&req, &mut resp, BufWriter::new(reader.get_mut()))
+                .map_err(HttpError::from)?;
+
+            if !keep_alive {
+                return Ok(());
+            }
+        }
     }
 
-    fn recv<T: Read>(mut reader: BufReader<T>) -> Result<HttpRequest, HttpError> {
-        let mut line = String::new();
-        reader.read_line(&mut line).map_err(HttpError::from)?;
-        let mut iter = line.trim_end_matches("\r\n").splitn(3, " ");
-        let method = match iter.next().ok_or_else(|| HttpError::HttpSyntaxError)? {
-            "GET" => HttpMethod::GET,
-            "POST" => HttpMethod::POST,
-            _ => return Err(HttpError::HttpSyntaxError),
+    fn recv<T: Read + Write>(&self, reader: &mut BufReader<T>) -> Result<RecvOutcome, HttpError> {
+        let lines = match read_head(reader)? {
+            Head::Closed => return Ok(RecvOutcome::Closed),
+            Head::Lines(lines) => lines,
+        };
+        let request_line = lines.first().ok_or(HttpError::HttpSyntaxError)?;
+        let mut iter = request_line.splitn(3, " ");
+        let method = HttpMethod::try_from(iter.next().ok_or(HttpError::HttpSyntaxError)?)?;
+        let target = iter.next().ok_or(HttpError::HttpSyntaxError)?.to_string();
+        let (path, query) = match target.split_once('?') {
+            Some((path, raw_query)) => (path.to_string(), QueryString::parse(raw_query)?),
+            None => (target, QueryString::empty()),
         };
-        let path = iter
-            .next()
-            .ok_or_else(|| HttpError::HttpSyntaxError)?
-            .to_string();
 
-        let version = HttpVersion::from(iter.next().ok_or_else(|| HttpError::HttpSyntaxError)?);
+        let version = HttpVersion::from(iter.next().ok_or(HttpError::HttpSyntaxError)?);
         if let HttpVersion::UNSUPPORTED = version {
             return Err(HttpError::HttpSyntaxError);
         }
         let mut headers = HttpHeaders::new();
 
-        headers.read_from(&mut reader)?;
-
-        let body = match headers.content_length() {
-            Some(0) => Vec::new(),
-            Some(v) => {
-                let mut body = Vec::with_capacity(v);
-                reader.read_to_end(&mut body)?;
-                body
+        headers.parse_lines(&lines[1..])?;
+
+        let has_body = headers.is_chunked() || headers.content_length().unwrap_or(0) > 0;
+        let expects_continue = headers
+            .get_first(&"expect".to_string())
+            .is_some_and(|v| v.eq_ignore_ascii_case("100-continue"));
+
+        if expects_continue && has_body {
+            let probe_req = HttpRequest {
+                method,
+                path,
+                version,
+                headers,
+                body: Box::new(Vec::new()),
+                params: HashMap::new(),
+                query,
+            };
+            if let Some(hook) = &self.expect_continue_hook {
+                if let Some(resp) = hook(&probe_req) {
+                    return Ok(RecvOutcome::Rejected(resp));
+                }
             }
-            None => Vec::new(),
-        };
+            let conn = reader.get_mut();
+            write!(conn, "HTTP/1.1 100 Continue\r\n\r\n")?;
+            conn.flush()?;
+
+            let HttpRequest {
+                method,
+                path,
+                version,
+                headers,
+                query,
+                ..
+            } = probe_req;
+            let body = read_body(reader, BodyReader::for_request(&headers))?;
+
+            return Ok(RecvOutcome::Request(HttpRequest {
+                method,
+                path,
+                version,
+                headers,
+                body: Box::new(body),
+                params: HashMap::new(),
+                query,
+            }));
+        }
 
-        Ok(HttpRequest {
-            method: method,
-            path: path,
-            version: version,
-            headers: headers,
-            body: body,
-        })
+        let body = read_body(reader, BodyReader::for_request(&headers))?;
+
+        Ok(RecvOutcome::Request(HttpRequest {
+            method,
+            path,
+            version,
+            headers,
+            body: Box::new(body),
+            params: HashMap::new(),
+            query,
+        }))
     }
 
-    fn send<T: Write>(resp: &HttpResponse, mut writer: BufWriter<T>) -> std::io::Result<()> {
+    fn send<T: Write>(
+        &self,
+        req: &HttpRequest,
+        resp: &mut HttpResponse,
+        mut writer: BufWriter<T>,
+    ) -> std::io::Result<()> {
         write!(
             writer,
             "{} {} {}\r\n",
             resp.version.string(),
             resp.status.code(),
-            resp.status.string()
+            resp.status.reason_phrase()
         )?;
         resp.headers.write_to(&mut writer)?;
-        if !resp.headers.contains_key(&"content-length".to_string()) {
-            write!(writer, "content-length: {}\r\n", resp.body.len())?;
+
+        let code = resp.status.code();
+        let no_body = code < 200 || code == 204 || code == 304;
+        if no_body {
+            write!(writer, "\r\n")?;
+            writer.flush()?;
+            return Ok(());
         }
 
-        write!(writer, "\r\n")?;
-        writer.write_all(&resp.body)?;
+        let encoding = if self.compression_enabled
+            && resp.body.size() != BodySize::Empty
+            && !resp.headers.contains_key(&"content-encoding".to_string())
+        {
+            best_encoding(req)
+        } else {
+            None
+        };
+
+        if let Some(e) = encoding {
+            // Compression requires the whole body up front regardless of
+            // how it was originally framed.
+            let raw = read_all_chunks(&mut *resp.body)?;
+            let compressed = compress_body(&raw, e)?;
+            write!(writer, "content-encoding: {}\r\n", e)?;
+            write!(writer, "vary: accept-encoding\r\n")?;
+            if !resp.headers.contains_key(&"content-length".to_string()) {
+                write!(writer, "content-length: {}\r\n", compressed.len())?;
+            }
+            write!(writer, "\r\n")?;
+            writer.write_all(&compressed)?;
+            writer.flush()?;
+            return Ok(());
+        }
+
+        match resp.body.size() {
+            BodySize::Empty => {
+                if !resp.headers.contains_key(&"content-length".to_string()) {
+                    write!(writer, "content-length: 0\r\n")?;
+                }
+                write!(writer, "\r\n")?;
+            }
+            BodySize::Sized(n) => {
+                if !resp.headers.contains_key(&"content-length".to_string()) {
+                    write!(writer, "content-length: {}\r\n", n)?;
+                }
+                write!(writer, "\r\n")?;
+                while let Some(chunk) = resp.body.next_chunk() {
+                    writer.write_all(&chunk?)?;
+                }
+            }
+            BodySize::Stream => {
+                if !resp.headers.contains_key(&"transfer-encoding".to_string()) {
+                    write!(writer, "transfer-encoding: chunked\r\n")?;
+                }
+                write!(writer, "\r\n")?;
+                while let Some(chunk) = resp.body.next_chunk() {
+                    write_chunk(&mut writer, &chunk?)?;
+                }
+                finish_chunked(&mut writer)?;
+            }
+        }
         writer.flush()?;
         Ok(())
     }
 }
 
+/// Picks the best codec the client advertised in `Accept-Encoding`,
+/// preferring gzip over deflate.
+fn best_encoding(req: &HttpRequest) -> Option<&'static str> {
+    let accept_encoding = req.headers.get_first(&"accept-encoding".to_string())?;
+    let offered: Vec<&str> = accept_encoding
+        .split(',')
+        .map(|s| s.split(';').next().unwrap_or("").trim())
+        .collect();
+    if offered.iter().any(|e| e.eq_ignore_ascii_case("gzip")) {
+        Some("gzip")
+    } else if offered.iter().any(|e| e.eq_ignore_ascii_case("deflate")) {
+        Some("deflate")
+    } else {
+        None
+    }
+}
+
+fn compress_body(body: &[u8], encoding: &str) -> std::io::Result<Vec<u8>> {
+    match encoding {
+        "gzip" => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body)?;
+            encoder.finish()
+        }
+        "deflate" => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body)?;
+            encoder.finish()
+        }
+        _ => Ok(body.to_vec()),
+    }
+}
+
+fn is_timeout(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+    )
+}
+
+/// Whether the connection should stay open after this request, per the
+/// HTTP/1.1 persistent-connection default (and the HTTP/1.0 opt-in).
+fn wants_keep_alive(req: &HttpRequest) -> bool {
+    let connection = req.headers.get_first(&"connection".to_string());
+    match connection {
+        Some(v) if v.eq_ignore_ascii_case("close") => false,
+        Some(v) if v.eq_ignore_ascii_case("keep-alive") => true,
+        _ => matches!(req.version, HttpVersion::HTTP1_1),
+    }
+}
+
 pub trait Handler {
     fn handle(&self, req: &mut HttpRequest) -> Result<HttpResponse, HttpError>;
 }
@@ -106,20 +410,71 @@ pub struct Router {
 
 impl Handler for Router {
     fn handle(&self, req: &mut HttpRequest) -> Result<HttpResponse, HttpError> {
+        let mut best: Option<(&Rule, HashMap<String, String>, u32)> = None;
         for rule in &self.rules {
-            if req.method == rule.method && req.path == rule.path {
-                return rule.handler.handle(req);
+            if req.method != rule.method {
+                continue;
+            }
+            if let Some(params) = match_path(&rule.path, &req.path) {
+                let specificity = rule.specificity();
+                if best
+                    .as_ref()
+                    .is_none_or(|(_, _, best_specificity)| specificity > *best_specificity)
+                {
+                    best = Some((rule, params, specificity));
+                }
             }
         }
+
+        if let Some((rule, params, _)) = best {
+            req.params = params;
+            return rule.handler.handle(req);
+        }
+
         Ok(HttpResponse {
             version: req.version,
             status: HttpStatus::NotFound,
             headers: HttpHeaders::new(),
-            body: Vec::new(),
+            body: Box::new(Vec::new()),
         })
     }
 }
 
+/// Matches a route pattern (e.g. `/users/:id` or `/files/*rest`) against a
+/// request path, returning the captured `:name`/`*name` segments on success.
+///
+/// A `:name` segment captures exactly one path segment; a `*name` segment
+/// greedily captures the remainder of the path (including slashes) and must
+/// be the last segment in the pattern.
+fn match_path(pattern: &str, path: &str) -> Option<HashMap<String, String>> {
+    let pattern_segments: Vec<&str> = pattern.split('/').collect();
+    let path_segments: Vec<&str> = path.split('/').collect();
+
+    let mut params = HashMap::new();
+    let mut i = 0;
+    while i < pattern_segments.len() {
+        let pattern_segment = pattern_segments[i];
+        if let Some(name) = pattern_segment.strip_prefix('*') {
+            params.insert(name.to_string(), path_segments[i..].join("/"));
+            return Some(params);
+        }
+
+        let path_segment = path_segments.get(i)?;
+        if let Some(name) = pattern_segment.strip_prefix(':') {
+            params.insert(name.to_string(), path_segment.to_string());
+        } else if pattern_segment != *path_segment {
+            return None;
+        }
+        i += 1;
+    }
+
+    if i == path_segments.len() {
+        Some(params)
+    } else {
+        None
+    }
+}
+
 impl Handler for fn(&mut HttpRequest) -> Result<HttpResponse, HttpError> {
     fn handle(&self, req: &mut HttpRequest) -> Result<HttpResponse, HttpError> {
         (self)(req)
@@ -131,26 +486,65 @@ impl Router {
         Router { rules: Vec::new() }
     }
 
+    /// # Panics
+    ///
+    /// Panics if `path` has a `*name` segment anywhere but last, since
+    /// `match_path` would silently ignore whatever follows it.
     pub fn add(
         &mut self,
         method: HttpMethod,
         path: String,
         handler: fn(&mut HttpRequest) -> Result<HttpResponse, HttpError>,
     ) {
+        assert!(
+            wildcard_is_last_segment(&path),
+            "route pattern {:?} has a *wildcard segment that isn't last",
+            path
+        );
         self.rules.push(Rule {
-            method: method,
-            path: path,
+            method,
+            path,
             handler: Box::new(handler),
         })
     }
 }
 
+/// Whether `pattern` has no `*name` segment, or has one only as its last
+/// segment.
+fn wildcard_is_last_segment(pattern: &str) -> bool {
+    let segments: Vec<&str> = pattern.split('/').collect();
+    match segments.iter().position(|s| s.starts_with('*')) {
+        Some(i) => i == segments.len() - 1,
+        None => true,
+    }
+}
+
 struct Rule {
     method: HttpMethod,
     path: String,
     handler: Box<dyn Handler>,
 }
 
+impl Rule {
+    /// Higher is more specific: a literal segment outranks a `:param`
+    /// segment, which outranks a `*wildcard` segment, so exact routes are
+    /// preferred over pattern routes that would also match.
+    fn specificity(&self) -> u32 {
+        self.path
+            .split('/')
+            .map(|segment| {
+                if segment.starts_with('*') {
+                    0
+                } else if segment.starts_with(':') {
+                    1
+                } else {
+                    2
+                }
+            })
+            .sum()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -168,6 +562,15 @@ mod tests {
         assert_eq!(String::from("/"), router.rules[0].path);
     }
 
+    #[test]
+    #[should_panic(expected = "has a *wildcard segment that isn't last")]
+    fn test_router_add_rejects_a_wildcard_that_is_not_the_last_segment() {
+        let mut router = Router::new();
+        let handler: fn(&mut HttpRequest) -> Result<HttpResponse, HttpError> =
+            |_| Err(HttpError::HttpSyntaxError);
+        router.add(HttpMethod::GET, String::from("/files/*rest/edit"), handler);
+    }
+
     fn test_router_handle_setup() -> Router {
         let mut router = Router::new();
 
@@ -176,7 +579,7 @@ mod tests {
                 version: HttpVersion::HTTP1_1,
                 status: HttpStatus::Ok,
                 headers: HttpHeaders::new(),
-                body: Vec::new(),
+                body: Box::new(Vec::new()),
             })
         };
         router.add(HttpMethod::GET, String::from("/ok"), handler);
@@ -193,21 +596,26 @@ mod tests {
             path: String::from("/ok"),
             version: HttpVersion::HTTP1_1,
             headers: HttpHeaders::new(),
-            body: Vec::new(),
+            body: Box::new(Vec::new()),
+            params: HashMap::new(),
+            query: QueryString::empty(),
         };
         let expected = HttpResponse {
             version: HttpVersion::HTTP1_1,
             status: HttpStatus::Ok,
             headers: HttpHeaders::new(),
-            body: Vec::new(),
+            body: Box::new(Vec::new()),
         };
 
-        let actual = router.handle(&mut req).unwrap();
+        let mut actual = router.handle(&mut req).unwrap();
 
         assert_eq!(expected.version, actual.version);
         assert_eq!(expected.status, actual.status);
         // assert_eq!(expected.headers, actual.headers);
-        assert_eq!(expected.body, actual.body);
+        assert_eq!(
+            Vec::<u8>::new(),
+            read_all_chunks(&mut *actual.body).unwrap()
+        );
     }
 
     #[test]
@@ -219,20 +627,186 @@ mod tests {
             path: String::from("/"),
             version: HttpVersion::HTTP1_1,
             headers: HttpHeaders::new(),
-            body: Vec::new(),
+            body: Box::new(Vec::new()),
+            params: HashMap::new(),
+            query: QueryString::empty(),
         };
         let expected = HttpResponse {
             version: HttpVersion::HTTP1_1,
             status: HttpStatus::NotFound,
             headers: HttpHeaders::new(),
-            body: Vec::new(),
+            body: Box::new(Vec::new()),
         };
 
-        let actual = router.handle(&mut req).unwrap();
+        let mut actual = router.handle(&mut req).unwrap();
 
         assert_eq!(expected.version, actual.version);
         assert_eq!(expected.status, actual.status);
         // assert_eq!(expected.headers, actual.headers);
-        assert_eq!(expected.body, actual.body);
+        assert_eq!(
+            Vec::<u8>::new(),
+            read_all_chunks(&mut *actual.body).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_match_path_captures_named_segment() {
+        let params = match_path("/users/:id", "/users/42").unwrap();
+        assert_eq!(Some(&"42".to_string()), params.get("id"));
+    }
+
+    #[test]
+    fn test_match_path_captures_wildcard_tail() {
+        let params = match_path("/files/*rest", "/files/a/b/c").unwrap();
+        assert_eq!(Some(&"a/b/c".to_string()), params.get("rest"));
+    }
+
+    #[test]
+    fn test_match_path_rejects_length_mismatch() {
+        assert_eq!(None, match_path("/users/:id", "/users"));
+    }
+
+    #[test]
+    fn test_router_handle_prefers_most_specific_rule() {
+        let mut router = Router::new();
+        router.add(HttpMethod::GET, String::from("/users/:id"), |req| {
+            Ok(HttpResponse {
+                version: HttpVersion::HTTP1_1,
+                status: HttpStatus::NotFound,
+                headers: HttpHeaders::new(),
+                body: Box::new(req.params.get("id").unwrap().clone().into_bytes()),
+            })
+        });
+        router.add(HttpMethod::GET, String::from("/users/me"), |_| {
+            Ok(HttpResponse {
+                version: HttpVersion::HTTP1_1,
+                status: HttpStatus::Ok,
+                headers: HttpHeaders::new(),
+                body: Box::new(Vec::new()),
+            })
+        });
+
+        let mut req = HttpRequest {
+            method: HttpMethod::GET,
+            path: String::from("/users/me"),
+            version: HttpVersion::HTTP1_1,
+            headers: HttpHeaders::new(),
+            body: Box::new(Vec::new()),
+            params: HashMap::new(),
+            query: QueryString::empty(),
+        };
+
+        let actual = router.handle(&mut req).unwrap();
+
+        assert_eq!(HttpStatus::Ok, actual.status);
+    }
+
+    #[test]
+    fn test_send_compresses_the_body_when_the_client_accepts_gzip_and_client_recv_decodes_it() {
+        let no_op: fn(&mut HttpRequest) -> Result<HttpResponse, HttpError> =
+            |_| Err(HttpError::HttpSyntaxError);
+        let server = HttpServer::new("127.0.0.1:0", Box::new(no_op))
+            .unwrap()
+            .with_compression();
+
+        let mut req = HttpRequest {
+            method: HttpMethod::GET,
+            path: String::from("/"),
+            version: HttpVersion::HTTP1_1,
+            headers: HttpHeaders::new(),
+            body: Box::new(Vec::new()),
+            params: HashMap::new(),
+            query: QueryString::empty(),
+        };
+        req.headers
+            .insert("accept-encoding".to_string(), "gzip".to_string());
+
+        let mut resp = HttpResponse {
+            version: HttpVersion::HTTP1_1,
+            status: HttpStatus::Ok,
+            headers: HttpHeaders::new(),
+            body: Box::new(b"hello, world".to_vec()),
+        };
+
+        let mut raw = Vec::new();
+        server
+            .send(&req, &mut resp, BufWriter::new(&mut raw))
+            .unwrap();
+
+        let mut decoded = crate::client::HttpClient::recv(BufReader::new(&raw[..])).unwrap();
+        assert_eq!(
+            Some("gzip"),
+            decoded.headers.get_first(&"content-encoding".to_string())
+        );
+        assert_eq!(
+            b"hello, world".to_vec(),
+            read_all_chunks(&mut *decoded.body).unwrap()
+        );
+    }
+
+    fn test_ok_handler() -> Box<dyn Handler> {
+        let handler: fn(&mut HttpRequest) -> Result<HttpResponse, HttpError> = |_| {
+            Ok(HttpResponse {
+                version: HttpVersion::HTTP1_1,
+                status: HttpStatus::Ok,
+                headers: HttpHeaders::new(),
+                body: Box::new(Vec::new()),
+            })
+        };
+        Box::new(handler)
+    }
+
+    #[test]
+    fn test_handle_serves_multiple_requests_on_a_keep_alive_connection() {
+        let server = HttpServer::new("127.0.0.1:0", test_ok_handler()).unwrap();
+        let addr = server.listener.local_addr().unwrap();
+
+        let client_thread = std::thread::spawn(move || {
+            let mut client = TcpStream::connect(addr).unwrap();
+            client
+                .write_all(b"GET /ok HTTP/1.1\r\nhost: example.com\r\n\r\n")
+                .unwrap();
+            client
+                .write_all(b"GET /ok HTTP/1.1\r\nhost: example.com\r\nconnection: close\r\n\r\n")
+                .unwrap();
+            let mut response = Vec::new();
+            client.read_to_end(&mut response).unwrap();
+            response
+        });
+
+        let (stream, _) = server.listener.accept().unwrap();
+        server.handle(stream).unwrap();
+        let response = client_thread.join().unwrap();
+
+        let text = String::from_utf8(response).unwrap();
+        assert_eq!(2, text.matches("HTTP/1.1 200 OK").count());
+    }
+
+    #[test]
+    fn test_handle_answers_a_slow_keep_alive_peer_with_408() {
+        let server = HttpServer::new("127.0.0.1:0", test_ok_handler())
+            .unwrap()
+            .with_read_timeout(Duration::from_millis(50));
+        let addr = server.listener.local_addr().unwrap();
+
+        let client_thread = std::thread::spawn(move || {
+            let mut client = TcpStream::connect(addr).unwrap();
+            client
+                .write_all(b"GET /ok HTTP/1.1\r\nhost: example.com\r\n\r\n")
+                .unwrap();
+            // Leave the connection open without sending a second request, so
+            // the server's read timeout fires while waiting for it.
+            let mut response = Vec::new();
+            client.read_to_end(&mut response).unwrap();
+            response
+        });
+
+        let (stream, _) = server.listener.accept().unwrap();
+        server.handle(stream).unwrap();
+        let response = client_thread.join().unwrap();
+
+        let text = String::from_utf8(response).unwrap();
+        assert_eq!(1, text.matches("HTTP/1.1 200 OK").count());
+        assert!(text.contains("HTTP/1.1 408 Request Timeout"));
     }
 }